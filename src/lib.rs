@@ -2,9 +2,11 @@
 //! a given closure
 
 use std::cell::Cell;
+use std::convert::TryFrom;
 use std::marker::PhantomData;
 use std::mem;
-use std::sync::atomic::{compiler_fence, Ordering};
+use std::sync::atomic::{compiler_fence, AtomicU8, AtomicUsize, Ordering};
+use std::sync::Arc;
 
 use arr_macro::arr;
 
@@ -14,14 +16,115 @@ use nix::sys::signal;
 pub use nix::sys::signal::{SaFlags, SigSet, Signal, SigAction, SigHandler};
 pub use nix::Error;
 
-thread_local!(static SIGNAL_HANDLERS: [Cell<Option<&'static dyn Fn(u8, &SigInfo)>>; 64] = arr![Cell::from(None); 64]);
+/// How many handlers may be stacked for a single signal at once within a thread.
+const STACK_DEPTH: usize = 8;
 
-extern "C" fn c_handler(signo: c_int, info: *mut SigInfo, _: *mut c_void) {
+type HandlerFn<'a> = dyn Fn(u8, &SigInfo) -> bool + 'a;
+
+/// A fixed-capacity, insertion-ordered stack of handlers for a single signal.
+///
+/// Entries are appended at `len` on install. On removal we shift the entries above the
+/// removed one down by one slot so relative ordering is preserved even when guards are
+/// dropped out of order; `c_handler` then walks from `len - 1` down to `0`, i.e. most
+/// recently installed first.
+#[derive(Clone, Copy)]
+struct HandlerStack {
+    entries: [Option<(u64, &'static HandlerFn<'static>)>; STACK_DEPTH],
+    len: usize,
+}
+
+impl HandlerStack {
+    const EMPTY: Self = HandlerStack {
+        entries: [None; STACK_DEPTH],
+        len: 0,
+    };
+
+    fn push(&mut self, id: u64, handler: &'static HandlerFn<'static>) -> Result<(), Error> {
+        if self.len >= STACK_DEPTH {
+            // too many SignalScopes stacked for this signal (max STACK_DEPTH); reported to the
+            // caller instead of panicking so a deeply nested caller gets a normal Result to handle
+            return Err(Error::ENOSPC);
+        }
+        self.entries[self.len] = Some((id, handler));
+        self.len += 1;
+        Ok(())
+    }
+
+    fn remove(&mut self, id: u64) {
+        if let Some(pos) = self.entries[..self.len]
+            .iter()
+            .position(|entry| entry.map(|(i, _)| i) == Some(id))
+        {
+            for i in pos..self.len - 1 {
+                self.entries[i] = self.entries[i + 1];
+            }
+            self.len -= 1;
+            self.entries[self.len] = None;
+        }
+    }
+}
+
+thread_local!(static SIGNAL_HANDLERS: [Cell<HandlerStack>; 64] = arr![Cell::new(HandlerStack::EMPTY); 64]);
+thread_local!(static NEXT_HANDLER_ID: Cell<u64> = Cell::new(0));
+
+// the disposition that was in place before we installed `c_handler` for a given signal, recorded
+// only for scopes that opted into `chain_to_previous`
+thread_local!(static PREV_ACTIONS: [Cell<Option<SigAction>>; 64] = arr![Cell::new(None); 64]);
+
+// the flags/mask the currently-innermost chaining scope installed `c_handler` with for a given
+// signal; used to rebuild an equivalent trampoline `sigaction` when chaining to SigDfl (see
+// `c_handler` below), since `PREV_ACTIONS` holds the disposition from *before* us, not our own
+thread_local!(static OWN_ACTIONS: [Cell<Option<SigAction>>; 64] = arr![Cell::new(None); 64]);
+
+extern "C" fn c_handler(signo: c_int, info: *mut SigInfo, ctx: *mut c_void) {
     // I hope the `with` method is async-signal-safe
-    SIGNAL_HANDLERS.with(|handlers| {
-        if let Some(h) = handlers[signo as usize].get() {
-            if let Some(info) = unsafe { info.as_ref() } {
-                h(signo as u8, info)
+    let consumed = SIGNAL_HANDLERS.with(|handlers| {
+        let stack = handlers[signo as usize].get();
+        if let Some(info) = unsafe { info.as_ref() } {
+            // most recently installed handler first; stop as soon as one consumes the signal
+            for (_, h) in stack.entries[..stack.len].iter().rev().flatten() {
+                if h(signo as u8, info) {
+                    return true;
+                }
+            }
+        }
+        false
+    });
+
+    if consumed {
+        return;
+    }
+
+    // nothing installed via a SignalScope wanted this signal; chain to whatever disposition was
+    // active before we were installed, as recorded by the innermost scope for this signal that
+    // opted in (scopes nested inside it that did not opt in leave that record in place)
+    PREV_ACTIONS.with(|prevs| {
+        if let Some(prev) = prevs[signo as usize].get() {
+            match prev.handler() {
+                SigHandler::SigDfl => {
+                    // there's no way to invoke the default disposition directly, so temporarily
+                    // restore it, re-raise, then put our trampoline back for the next delivery
+                    let dfl = SigAction::new(SigHandler::SigDfl, SaFlags::empty(), SigSet::empty());
+                    let signal = Signal::try_from(signo).expect("valid signal number");
+                    if unsafe { signal::sigaction(signal, &dfl) }.is_ok() {
+                        unsafe { libc::raise(signo) };
+                        // the default disposition didn't terminate the process (e.g. SIGCHLD), so
+                        // put our trampoline back for the next delivery, using the flags/mask this
+                        // scope itself installed (not `prev`'s, which belong to whatever was there
+                        // before us and may be missing flags like SA_ONSTACK/SA_NODEFER we need)
+                        let own = OWN_ACTIONS.with(|actions| actions[signo as usize].get());
+                        if let Some(own) = own {
+                            let ours =
+                                SigAction::new(SigHandler::SigAction(c_handler), own.flags(), own.mask());
+                            let _ = unsafe { signal::sigaction(signal, &ours) };
+                        }
+                    }
+                }
+                SigHandler::SigIgn => {
+                    // honor the prior disposition by doing nothing
+                }
+                SigHandler::Handler(f) => f(signo),
+                SigHandler::SigAction(f) => f(signo, info, ctx),
             }
         }
     });
@@ -29,88 +132,870 @@ extern "C" fn c_handler(signo: c_int, info: *mut SigInfo, _: *mut c_void) {
 
 // this struct is necessary in the event of an unwinding panic to ensure the handler fn is not left
 // in thread local storage longer than its lifetime
-struct HandlerGuard<'f, F: Fn(u8, &SigInfo) + 'f> {
-    signal: Signal,
+struct HandlerGuard<'f, F: Fn(u8, &SigInfo) -> bool + 'f> {
+    // (signal, id) for every signal this guard installed the handler for
+    entries: Vec<(Signal, u64)>,
     handler: PhantomData<&'f F>,
-    old: Option<&'static dyn Fn(u8, &SigInfo)>,
-}
-
-impl<'f, F: Fn(u8, &SigInfo) + 'f> HandlerGuard<'f, F> {
-    fn install(signal: Signal, handler: &'f F) -> Self {
-        let old = SIGNAL_HANDLERS.with(|handlers| {
-            let fn_object = handler as &dyn Fn(u8, &SigInfo);
-            // safe because we ensure the value is only stored in the variable for the lifetime of this
-            // object, and we do not leak the reference anywhere else
-            let static_fn = unsafe {
-                // type/lifetime annotations save lives when using mem::transmute
-                mem::transmute::<&'f dyn Fn(u8, &SigInfo), &'static dyn Fn(u8, &SigInfo)>(fn_object)
-            };
-            handlers[signal as usize].replace(Some(static_fn))
-        });
-        Self {
-            signal,
-            handler: Default::default(),
-            old,
+}
+
+impl<'f, F: Fn(u8, &SigInfo) -> bool + 'f> HandlerGuard<'f, F> {
+    fn install(signals: &[Signal], handler: &'f F) -> Result<Self, Error> {
+        // make sure every signal in `signals` has room for one more handler before mutating any
+        // TLS state, so a capacity failure can never leave a 'static reference to `handler`
+        // dangling in SIGNAL_HANDLERS for a signal we already pushed onto (a signal can repeat in
+        // `signals`, hence `pending`: how many more slots that signal still needs from the entries
+        // ahead of it in this same call)
+        for (i, &signal) in signals.iter().enumerate() {
+            let pending = signals[..=i].iter().filter(|&&s| s == signal).count();
+            let len = SIGNAL_HANDLERS.with(|handlers| handlers[signal as usize].get().len);
+            if len + pending > STACK_DEPTH {
+                return Err(Error::ENOSPC);
+            }
         }
+
+        let fn_object = handler as &dyn Fn(u8, &SigInfo) -> bool;
+        // safe because we ensure the value is only stored in the variable for the lifetime of this
+        // object, and we do not leak the reference anywhere else
+        let static_fn = unsafe {
+            // type/lifetime annotations save lives when using mem::transmute
+            mem::transmute::<&'f HandlerFn<'f>, &'static HandlerFn<'static>>(fn_object)
+        };
+        let entries = signals
+            .iter()
+            .map(|&signal| {
+                let id = NEXT_HANDLER_ID.with(|next| {
+                    let id = next.get();
+                    next.set(id + 1);
+                    id
+                });
+                SIGNAL_HANDLERS.with(|handlers| {
+                    let mut stack = handlers[signal as usize].get();
+                    // capacity was already validated for every signal above
+                    stack
+                        .push(id, static_fn)
+                        .expect("capacity was validated above");
+                    handlers[signal as usize].set(stack);
+                });
+                (signal, id)
+            })
+            .collect();
+        Ok(Self {
+            entries,
+            handler: Default::default(),
+        })
     }
 }
 
-impl<'f, F: Fn(u8, &SigInfo) + 'f> Drop for HandlerGuard<'f, F> {
+impl<'f, F: Fn(u8, &SigInfo) -> bool + 'f> Drop for HandlerGuard<'f, F> {
     // drop handlers get run on unwind YEET
     fn drop(&mut self) {
-        // pull the signal handler we installed back out
-        let _mine = SIGNAL_HANDLERS
-            .with(|handlers| handlers[self.signal as usize].replace(self.old.take()));
+        // pull our entries back out, tolerating guards that were installed and dropped out of order
+        for &(signal, id) in &self.entries {
+            SIGNAL_HANDLERS.with(|handlers| {
+                let mut stack = handlers[signal as usize].get();
+                stack.remove(id);
+                handlers[signal as usize].set(stack);
+            });
+        }
+    }
+}
+
+// restores, for every signal a scope installed `c_handler` for, the `sigaction` disposition that
+// was in place beforehand, even if the scope's closure unwinds
+struct RestoreActionGuard {
+    entries: Vec<(Signal, SigAction)>,
+}
+
+impl RestoreActionGuard {
+    fn new(entries: Vec<(Signal, SigAction)>) -> Self {
+        Self { entries }
+    }
+}
+
+impl Drop for RestoreActionGuard {
+    fn drop(&mut self) {
+        // in reverse, so a signal listed more than once ends up with the disposition from before
+        // its first entry rather than our own trampoline
+        for &(signal, old) in self.entries.iter().rev() {
+            // nothing we can do if this fails and we're already unwinding/returning
+            let _ = unsafe { signal::sigaction(signal, &old) };
+        }
+    }
+}
+
+// records, per signal, the disposition `c_handler` should chain to when every installed handler
+// declines a delivery, and the flags/mask `c_handler` itself was installed with, for scopes that
+// opted into `chain_to_previous`; restores whatever was recorded by an enclosing scope (or
+// nothing) for each signal on drop
+struct ChainGuard {
+    entries: Vec<(Signal, Option<SigAction>, Option<SigAction>)>,
+}
+
+impl ChainGuard {
+    fn install(installed: &[(Signal, SigAction)], ours: SigAction) -> Self {
+        let entries = installed
+            .iter()
+            .map(|&(signal, prev)| {
+                // `prev` is our own trampoline when an enclosing scope (or an earlier repeat of
+                // this signal) already installed it; chaining to that would re-enter `c_handler`
+                // forever, so keep whatever an enclosing chaining scope recorded instead
+                let nested = matches!(
+                    prev.handler(),
+                    SigHandler::SigAction(f) if f as usize == c_handler as usize
+                );
+                let old_prev = PREV_ACTIONS.with(|prevs| {
+                    let old = prevs[signal as usize].get();
+                    if !nested {
+                        prevs[signal as usize].set(Some(prev));
+                    }
+                    old
+                });
+                let old_own = OWN_ACTIONS.with(|actions| actions[signal as usize].replace(Some(ours)));
+                (signal, old_prev, old_own)
+            })
+            .collect();
+        Self { entries }
+    }
+}
+
+impl Drop for ChainGuard {
+    fn drop(&mut self) {
+        for &(signal, old_prev, old_own) in self.entries.iter().rev() {
+            PREV_ACTIONS.with(|prevs| prevs[signal as usize].set(old_prev));
+            OWN_ACTIONS.with(|actions| actions[signal as usize].set(old_own));
+        }
+    }
+}
+
+// allocates an alternate signal stack for the duration of a scope and restores whatever
+// alternate stack (if any) was configured beforehand on drop, even if the scope's closure unwinds
+struct AltStackGuard {
+    old: libc::stack_t,
+    // kept alive only so the buffer isn't freed while the alternate stack might still be in use;
+    // never read directly
+    _stack: Vec<u8>,
+}
+
+impl AltStackGuard {
+    fn install(size: usize) -> Result<Self, Error> {
+        let mut stack = vec![0u8; size];
+        let new = libc::stack_t {
+            ss_sp: stack.as_mut_ptr() as *mut c_void,
+            ss_flags: 0,
+            ss_size: size,
+        };
+        let mut old = unsafe { mem::zeroed::<libc::stack_t>() };
+        if unsafe { libc::sigaltstack(&new, &mut old) } != 0 {
+            return Err(Error::last());
+        }
+        Ok(Self { old, _stack: stack })
     }
 }
 
-/// Install a signal handler only valid for a given scope
+impl Drop for AltStackGuard {
+    fn drop(&mut self) {
+        // nothing we can do if this fails and we're already unwinding/returning
+        let _ = unsafe { libc::sigaltstack(&self.old, std::ptr::null_mut()) };
+    }
+}
+
+/// Install a signal handler, shared across one or more signals, only valid for a given scope
 pub struct SignalScope<F> {
     handler: F,
-    signal: Signal,
+    signals: Vec<Signal>,
     flags: SaFlags,
     set: SigSet,
+    chain: bool,
+    alt_stack_size: Option<usize>,
 }
 
-impl<Handler: Fn(u8, &SigInfo)> SignalScope<Handler> {
-    /// Create an object representing the provided signal handler.
+impl<Handler: Fn(u8, &SigInfo) -> bool> SignalScope<Handler> {
+    /// Create an object representing the provided signal handler, installed for every signal in
+    /// `signals` (e.g. a `SigSet`, or any other `IntoIterator<Item = Signal>` such as an array).
+    /// This lets a single scope cover a family of signals, like `SIGINT`/`SIGTERM`/`SIGHUP`, with
+    /// shared closure state instead of nesting a scope per signal.
+    ///
     /// The handler is only called in the thread that run() is called from.
     /// If another thread receives the same signal, the signal will be ignored unless the thread
     /// is using its own SignalScope for the same signal
     ///
+    /// `handler` returns `true` if it consumed the signal, or `false` to let it fall through to
+    /// any handler installed by an outer `SignalScope` for the same signal on this thread. This
+    /// lets scopes for the same signal nest and compose like a middleware chain instead of the
+    /// inner one silently shadowing the outer one.
+    ///
     /// `set` defines what signals are blocked during the execution of the signal handler itself
     ///
     /// See `sigaction(3P)` for more info
     /// # Safety
     /// This is an unsafe operation because the passed `handler` must only call async-signal-safe
     /// functions and we cannot verify this
-    pub unsafe fn new(signal: Signal, flags: SaFlags, set: SigSet, handler: Handler) -> Self {
+    pub unsafe fn new<S: IntoIterator<Item = Signal>>(
+        signals: S,
+        flags: SaFlags,
+        set: SigSet,
+        handler: Handler,
+    ) -> Self {
         Self {
             handler,
-            signal,
+            signals: signals.into_iter().collect(),
             flags,
             set,
+            chain: false,
+            alt_stack_size: None,
         }
     }
 
+    /// Chain to whatever signal disposition was active before this scope, for deliveries that
+    /// every installed handler for that signal declines (all return `false`).
+    ///
+    /// If the prior disposition was `SigDfl` or `SigIgn` it is honored as-is; if it was another
+    /// handler (e.g. one installed by another library), that handler is called with the forwarded
+    /// `signo`/`info`. When this scope is nested inside another `SignalScope` for the same signal,
+    /// the enclosing scope's handler has already been offered each delivery, so chaining goes on
+    /// to whatever an enclosing chaining scope recorded, if any. This lets a `SignalScope` cooperate with
+    /// handlers installed outside of this crate instead of permanently stealing the signal.
+    pub fn chain_to_previous(mut self) -> Self {
+        self.chain = true;
+        self
+    }
+
+    /// Run the handler on an alternate signal stack of `size` bytes instead of the thread's
+    /// normal stack, adding `SaFlags::SA_ONSTACK` to the flags passed to `sigaction`.
+    ///
+    /// This is required to handle a signal (e.g. `SIGSEGV` from a stack overflow) in a thread
+    /// whose normal stack may already be exhausted or corrupted. The scope allocates and owns
+    /// the alternate stack for the lifetime of `run`, installing it on entry and restoring
+    /// whatever alternate stack was configured before (including unwind) on exit, so nested
+    /// scopes compose correctly.
+    pub fn on_alt_stack(mut self, size: usize) -> Self {
+        self.alt_stack_size = Some(size);
+        self
+    }
+
     /// Run the given closure with this signal handler installed
     pub fn run<T, F: FnOnce() -> T>(self, f: F) -> Result<T, Error> {
+        // install the alternate stack first, if requested, so SA_ONSTACK has somewhere to land
+        let alt_stack_guard = self.alt_stack_size.map(AltStackGuard::install).transpose()?;
+        let flags = if alt_stack_guard.is_some() {
+            self.flags | SaFlags::SA_ONSTACK
+        } else {
+            self.flags
+        };
+        // chaining to SigDfl works by temporarily reinstalling the default disposition and
+        // re-raising the signal from inside c_handler (see the SigDfl arm below). Without
+        // SA_NODEFER the signal being handled is implicitly added to the mask for the duration of
+        // the handler, so that raise() only marks it pending instead of delivering it; by the time
+        // it unblocks we've already put our trampoline back, and the pending signal re-enters
+        // c_handler instead of reaching SigDfl, looping forever. SA_NODEFER keeps the signal
+        // unblocked so the re-raise is delivered synchronously, within the raise() call itself.
+        let flags = if self.chain {
+            flags | SaFlags::SA_NODEFER
+        } else {
+            flags
+        };
+
         let action = SigHandler::SigAction(c_handler);
-        let sa = SigAction::new(action, self.flags, self.set);
+        let sa = SigAction::new(action, flags, self.set);
+
+        // load our handler into every signal's TLS stack before touching any OS disposition
+        let guard = HandlerGuard::install(&self.signals, &self.handler)?;
 
-        // load our handler
-        let guard = HandlerGuard::install(self.signal, &self.handler);
-        let _old_handler = unsafe { signal::sigaction(self.signal, &sa)? };
+        // sigaction each signal in turn, unwinding anything we already installed if one fails
+        let mut installed = Vec::with_capacity(self.signals.len());
+        for &signal in &self.signals {
+            match unsafe { signal::sigaction(signal, &sa) } {
+                Ok(old) => installed.push((signal, old)),
+                Err(e) => {
+                    for &(signal, old) in installed.iter().rev() {
+                        let _ = unsafe { signal::sigaction(signal, &old) };
+                    }
+                    drop(guard);
+                    return Err(e);
+                }
+            }
+        }
+
+        let restore_guard = RestoreActionGuard::new(installed.clone());
+        let chain_guard = self.chain.then(|| ChainGuard::install(&installed, sa));
 
         compiler_fence(Ordering::SeqCst);
         let ret = Ok(f());
         compiler_fence(Ordering::SeqCst);
 
-        // uninstall the handler fn from TLS
+        // unwind in the reverse order we installed: stop chaining, restore the previous
+        // sigactions, uninstall the handler fn from TLS, then tear down the alternate stack
+        drop(chain_guard);
+        drop(restore_guard);
         drop(guard);
-
-        // we no longer reinstall the old handler
+        drop(alt_stack_guard);
 
         ret
     }
 }
+
+/// Number of deliveries [`signal_queue`]'s ring buffer can hold before it starts dropping events.
+const QUEUE_CAPACITY: usize = 256;
+
+// `seq` is the Vyukov bounded-queue sequence number for this slot: it starts out equal to the
+// slot's index (the position the producer will first write here), becomes `position + 1` once
+// `push` has published a delivery into `signo`/`info`, and becomes `position + QUEUE_CAPACITY`
+// once a consumer has claimed and fully read it, marking the slot free for the producer to reuse
+// on the next lap. This is what lets `try_recv` tell "ready to read" apart from "already claimed
+// by another consumer" apart from "not pushed yet", instead of just racing a bare `head` index
+// against a `push` that could already be overwriting the same slot on the next lap.
+struct QueueSlot {
+    seq: AtomicUsize,
+    signo: AtomicU8,
+    info: Cell<SigInfo>,
+}
+
+impl QueueSlot {
+    fn new(index: usize) -> Self {
+        QueueSlot {
+            seq: AtomicUsize::new(index),
+            signo: AtomicU8::new(0),
+            info: Cell::new(unsafe { mem::zeroed() }),
+        }
+    }
+}
+
+// Safety: `push` only ever writes to a slot after observing `seq == tail` (i.e. no consumer
+// still holds a claim on it), and `try_recv` only ever reads a slot after winning the
+// compare-exchange claim on `head` for that position, so two parties never touch the same slot's
+// `Cell` concurrently.
+unsafe impl Sync for QueueSlot {}
+
+// A fixed-capacity, lock-free single-producer/multi-consumer ring buffer, using the bounded MPMC
+// queue design from Dmitry Vyukov's "Bounded MPMC queue" (1024cores.net). `push` is the only
+// thing that runs inside the signal handler, and it performs nothing but atomic loads/stores and
+// a `Copy` into a preallocated slot, so it is async-signal-safe. Consumers race to claim
+// deliveries via a compare-exchange loop on `head`, guarded by each slot's own `seq` counter, so a
+// `SignalReceiver` may be freely cloned and drained from multiple threads.
+struct Ring {
+    slots: Box<[QueueSlot; QUEUE_CAPACITY]>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    overflow: AtomicUsize,
+}
+
+impl Ring {
+    fn new() -> Self {
+        let mut index = 0usize;
+        Ring {
+            slots: Box::new(arr![{ let slot = QueueSlot::new(index); index += 1; slot }; 256]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            overflow: AtomicUsize::new(0),
+        }
+    }
+
+    fn push(&self, signo: u8, info: &SigInfo) {
+        // single producer, so `tail` only ever needs to be read/written by us; no CAS required
+        let tail = self.tail.load(Ordering::Relaxed);
+        let slot = &self.slots[tail % QUEUE_CAPACITY];
+        if slot.seq.load(Ordering::Acquire) != tail {
+            // the slot from `QUEUE_CAPACITY` deliveries ago hasn't been fully read by a consumer
+            // yet; drop the event rather than overwrite a delivery that's still being claimed
+            self.overflow.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        slot.info.set(*info);
+        slot.signo.store(signo, Ordering::Relaxed);
+        // publish: any consumer that observes `seq == tail + 1` is guaranteed to see the writes
+        // above
+        slot.seq.store(tail + 1, Ordering::Release);
+        self.tail.store(tail.wrapping_add(1), Ordering::Relaxed);
+    }
+
+    fn try_recv(&self) -> Option<(u8, SigInfo)> {
+        let mut head = self.head.load(Ordering::Relaxed);
+        let slot = loop {
+            let slot = &self.slots[head % QUEUE_CAPACITY];
+            let seq = slot.seq.load(Ordering::Acquire);
+            match seq.wrapping_sub(head.wrapping_add(1)) {
+                // this slot holds an unclaimed delivery; try to claim it before reading
+                0 => {
+                    match self.head.compare_exchange_weak(
+                        head,
+                        head.wrapping_add(1),
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                    ) {
+                        Ok(_) => break slot,
+                        // another consumer claimed it first; retry with the updated head
+                        Err(current) => head = current,
+                    }
+                }
+                // `seq` hasn't caught up to `head + 1` yet: nothing has been pushed here since
+                // the last lap, i.e. the queue is empty from our point of view
+                diff if (diff as isize) < 0 => return None,
+                // `seq` is already ahead of `head + 1`: another consumer has already claimed and
+                // released this slot since we last loaded `head`; reload and retry
+                _ => head = self.head.load(Ordering::Relaxed),
+            }
+        };
+        let signo = slot.signo.load(Ordering::Relaxed);
+        let info = slot.info.get();
+        // release the slot back to the producer, who may reuse it starting at `head + CAPACITY`
+        slot.seq.store(head + QUEUE_CAPACITY, Ordering::Release);
+        Some((signo, info))
+    }
+}
+
+/// A handle for draining the signals recorded by a [`signal_queue`] scope.
+///
+/// Cloning a `SignalReceiver` shares the same underlying ring buffer; clones may be handed to
+/// multiple threads and drained concurrently, since consumers claim each delivery via a
+/// compare-exchange loop instead of assuming a single reader.
+#[derive(Clone)]
+pub struct SignalReceiver {
+    ring: Arc<Ring>,
+}
+
+impl SignalReceiver {
+    /// Remove and return the oldest queued signal, without blocking.
+    pub fn try_recv(&self) -> Option<(u8, SigInfo)> {
+        self.ring.try_recv()
+    }
+
+    /// Block the calling thread, spinning until a signal is available, then remove and return it.
+    pub fn recv(&self) -> (u8, SigInfo) {
+        loop {
+            if let Some(event) = self.try_recv() {
+                return event;
+            }
+            std::hint::spin_loop();
+        }
+    }
+
+    /// Remove and return every signal currently queued, without blocking for more to arrive.
+    pub fn drain(&self) -> impl Iterator<Item = (u8, SigInfo)> + '_ {
+        std::iter::from_fn(move || self.try_recv())
+    }
+
+    /// Number of deliveries dropped because the ring buffer was full when they arrived.
+    pub fn overflow_count(&self) -> usize {
+        self.ring.overflow.load(Ordering::Relaxed)
+    }
+}
+
+/// Build a [`SignalScope`] whose handler performs no user logic: it only copies the delivery
+/// into a preallocated, lock-free ring buffer that can be drained from ordinary (non-handler)
+/// code via the returned [`SignalReceiver`].
+///
+/// Unlike [`SignalScope::new`] this is safe to call: the handler installed here only ever does
+/// async-signal-safe work (atomic loads/stores and a `Copy` into a preallocated buffer), so there
+/// is no way for it to violate async-signal-safety the way an arbitrary user closure could.
+pub fn signal_queue<S: IntoIterator<Item = Signal>>(
+    signals: S,
+    flags: SaFlags,
+    set: SigSet,
+) -> (SignalScope<impl Fn(u8, &SigInfo) -> bool>, SignalReceiver) {
+    let ring = Arc::new(Ring::new());
+    let handler_ring = ring.clone();
+    let handler = move |signo: u8, info: &SigInfo| {
+        handler_ring.push(signo, info);
+        true
+    };
+    // Safety: `handler` above only performs async-signal-safe work
+    let scope = unsafe { SignalScope::new(signals, flags, set, handler) };
+    (scope, SignalReceiver { ring })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn handler(id: u64) -> &'static HandlerFn<'static> {
+        // the contents are never called in these tests; only identity (the `id` tag passed
+        // alongside it into the stack) is exercised
+        Box::leak(Box::new(move |_: u8, _: &SigInfo| id == u64::MAX))
+    }
+
+    #[test]
+    fn handler_stack_pops_most_recent_first() {
+        let mut stack = HandlerStack::EMPTY;
+        stack.push(1, handler(1)).unwrap();
+        stack.push(2, handler(2)).unwrap();
+        stack.push(3, handler(3)).unwrap();
+        let ids: Vec<u64> = stack.entries[..stack.len]
+            .iter()
+            .rev()
+            .flatten()
+            .map(|&(id, _)| id)
+            .collect();
+        assert_eq!(ids, vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn handler_stack_remove_preserves_order_of_survivors() {
+        let mut stack = HandlerStack::EMPTY;
+        stack.push(1, handler(1)).unwrap();
+        stack.push(2, handler(2)).unwrap();
+        stack.push(3, handler(3)).unwrap();
+        stack.remove(2);
+        let ids: Vec<u64> = stack.entries[..stack.len]
+            .iter()
+            .flatten()
+            .map(|&(id, _)| id)
+            .collect();
+        assert_eq!(ids, vec![1, 3]);
+    }
+
+    #[test]
+    fn handler_stack_remove_tolerates_unknown_id() {
+        let mut stack = HandlerStack::EMPTY;
+        stack.push(1, handler(1)).unwrap();
+        stack.remove(999);
+        assert_eq!(stack.len, 1);
+    }
+
+    #[test]
+    fn handler_stack_rejects_push_past_capacity() {
+        let mut stack = HandlerStack::EMPTY;
+        for i in 0..STACK_DEPTH as u64 {
+            stack.push(i, handler(i)).unwrap();
+        }
+        assert_eq!(stack.push(STACK_DEPTH as u64, handler(0)), Err(Error::ENOSPC));
+    }
+
+    fn sig_info() -> SigInfo {
+        unsafe { mem::zeroed() }
+    }
+
+    #[test]
+    fn ring_round_trips_in_fifo_order() {
+        let ring = Ring::new();
+        ring.push(1, &sig_info());
+        ring.push(2, &sig_info());
+        assert_eq!(ring.try_recv().map(|(signo, _)| signo), Some(1));
+        assert_eq!(ring.try_recv().map(|(signo, _)| signo), Some(2));
+        assert_eq!(ring.try_recv(), None);
+    }
+
+    #[test]
+    fn ring_counts_overflow_instead_of_blocking() {
+        let ring = Ring::new();
+        for _ in 0..QUEUE_CAPACITY + 5 {
+            ring.push(7, &sig_info());
+        }
+        assert_eq!(ring.overflow.load(Ordering::Relaxed), 5);
+        let mut received = 0;
+        while ring.try_recv().is_some() {
+            received += 1;
+        }
+        assert_eq!(received, QUEUE_CAPACITY);
+    }
+
+    #[test]
+    fn ring_try_recv_is_safe_for_concurrent_consumers() {
+        let ring = Arc::new(Ring::new());
+        // fill to capacity rather than overflowing it, so every push below is guaranteed to land
+        let total = QUEUE_CAPACITY;
+        for _ in 0..total {
+            ring.push(1, &sig_info());
+        }
+
+        let seen = Arc::new(AtomicUsize::new(0));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let ring = ring.clone();
+                let seen = seen.clone();
+                std::thread::spawn(move || {
+                    let mut count = 0;
+                    while ring.try_recv().is_some() {
+                        count += 1;
+                    }
+                    seen.fetch_add(count, Ordering::Relaxed);
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+        assert_eq!(ring.try_recv(), None);
+        assert_eq!(seen.load(Ordering::Relaxed), total);
+    }
+
+    #[test]
+    fn ring_survives_concurrent_push_and_drain_without_corruption() {
+        // a single producer keeps pushing, wrapping around the ring many times over, while
+        // several consumers race to drain concurrently; each delivery tags `si_code` with twice
+        // its `signo` so a consumer that ever reads a stale/mismatched (signo, info) pair (the
+        // bug this test guards against) is caught immediately instead of silently passing
+        const ITERATIONS: usize = QUEUE_CAPACITY * 50;
+        let ring = Arc::new(Ring::new());
+
+        let producer = {
+            let ring = ring.clone();
+            std::thread::spawn(move || {
+                for i in 0..ITERATIONS {
+                    let signo = (i % 256) as u8;
+                    let mut info = sig_info();
+                    info.si_code = signo as i32 * 2;
+                    ring.push(signo, &info);
+                }
+            })
+        };
+
+        let seen = Arc::new(AtomicUsize::new(0));
+        let consumers: Vec<_> = (0..4)
+            .map(|_| {
+                let ring = ring.clone();
+                let seen = seen.clone();
+                std::thread::spawn(move || {
+                    let mut count = 0;
+                    // give the producer a little longer than it needs to finish, then drain
+                    // whatever is left once it has
+                    for _ in 0..ITERATIONS * 10 {
+                        if let Some((signo, info)) = ring.try_recv() {
+                            assert_eq!(info.si_code, signo as i32 * 2, "stale/mismatched delivery");
+                            count += 1;
+                        }
+                    }
+                    seen.fetch_add(count, Ordering::Relaxed);
+                })
+            })
+            .collect();
+
+        producer.join().unwrap();
+        for c in consumers {
+            c.join().unwrap();
+        }
+        while let Some((signo, info)) = ring.try_recv() {
+            assert_eq!(info.si_code, signo as i32 * 2, "stale/mismatched delivery");
+            seen.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let overflow = ring.overflow.load(Ordering::Relaxed);
+        assert_eq!(seen.load(Ordering::Relaxed) + overflow, ITERATIONS);
+    }
+
+    // `sigaction` dispositions are process-wide, so any test that installs a real `SignalScope`
+    // and raises a signal must not run concurrently with another one doing the same, even though
+    // `cargo test` otherwise runs tests in parallel threads by default
+    static SIGNAL_TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    #[test]
+    fn scope_stacks_handlers_and_falls_through_on_decline() {
+        let _guard = SIGNAL_TEST_LOCK.lock().unwrap();
+        let outer_calls = Arc::new(AtomicUsize::new(0));
+        let inner_calls = Arc::new(AtomicUsize::new(0));
+        let outer_calls2 = outer_calls.clone();
+        let inner_calls2 = inner_calls.clone();
+
+        let outer = unsafe {
+            SignalScope::new(
+                [Signal::SIGUSR1],
+                SaFlags::empty(),
+                SigSet::empty(),
+                move |_, _| {
+                    outer_calls2.fetch_add(1, Ordering::SeqCst);
+                    true
+                },
+            )
+        };
+
+        outer
+            .run(|| {
+                // the inner scope declines every delivery, so it must fall through to `outer`
+                // instead of shadowing it
+                let inner = unsafe {
+                    SignalScope::new(
+                        [Signal::SIGUSR1],
+                        SaFlags::empty(),
+                        SigSet::empty(),
+                        move |_, _| {
+                            inner_calls2.fetch_add(1, Ordering::SeqCst);
+                            false
+                        },
+                    )
+                };
+                inner
+                    .run(|| unsafe { libc::raise(Signal::SIGUSR1 as c_int) })
+                    .unwrap();
+
+                // the inner scope has now dropped; only `outer` is installed for this raise
+                unsafe { libc::raise(Signal::SIGUSR1 as c_int) };
+            })
+            .unwrap();
+
+        assert_eq!(inner_calls.load(Ordering::SeqCst), 1);
+        assert_eq!(outer_calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn scope_restores_prior_disposition_on_exit() {
+        let _guard = SIGNAL_TEST_LOCK.lock().unwrap();
+        unsafe { libc::signal(Signal::SIGUSR1 as c_int, libc::SIG_IGN) };
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls2 = calls.clone();
+        let scope = unsafe {
+            SignalScope::new(
+                [Signal::SIGUSR1],
+                SaFlags::empty(),
+                SigSet::empty(),
+                move |_, _| {
+                    calls2.fetch_add(1, Ordering::SeqCst);
+                    true
+                },
+            )
+        };
+        scope
+            .run(|| unsafe { libc::raise(Signal::SIGUSR1 as c_int) })
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // the scope has exited, so the SIG_IGN disposition that was in place before it must be
+        // back; raising again should therefore be silently ignored rather than reaching our
+        // handler (or falling back to the default, which would terminate the test process)
+        unsafe { libc::raise(Signal::SIGUSR1 as c_int) };
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        unsafe { libc::signal(Signal::SIGUSR1 as c_int, libc::SIG_DFL) };
+    }
+
+    #[test]
+    fn chained_scope_falls_through_to_prior_sigign_when_declined() {
+        let _guard = SIGNAL_TEST_LOCK.lock().unwrap();
+        unsafe { libc::signal(Signal::SIGUSR1 as c_int, libc::SIG_IGN) };
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls2 = calls.clone();
+        let scope = unsafe {
+            SignalScope::new(
+                [Signal::SIGUSR1],
+                SaFlags::empty(),
+                SigSet::empty(),
+                move |_, _| {
+                    calls2.fetch_add(1, Ordering::SeqCst);
+                    false // always decline, so every delivery chains to the prior SIG_IGN
+                },
+            )
+        }
+        .chain_to_previous();
+
+        scope
+            .run(|| unsafe { libc::raise(Signal::SIGUSR1 as c_int) })
+            .unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        unsafe { libc::signal(Signal::SIGUSR1 as c_int, libc::SIG_DFL) };
+    }
+
+    #[test]
+    fn chained_scope_honors_prior_sigdfl_without_livelock() {
+        let _guard = SIGNAL_TEST_LOCK.lock().unwrap();
+        // SIGCHLD's default disposition is to be ignored, so chaining to it exercises the SigDfl
+        // arm of `c_handler` (the re-raise-against-SigDfl dance) without risking the test process
+        unsafe { libc::signal(Signal::SIGCHLD as c_int, libc::SIG_DFL) };
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls2 = calls.clone();
+        let scope = unsafe {
+            SignalScope::new(
+                [Signal::SIGCHLD],
+                SaFlags::empty(),
+                SigSet::empty(),
+                move |_, _| {
+                    calls2.fetch_add(1, Ordering::SeqCst);
+                    false // always decline, so every delivery chains to SigDfl
+                },
+            )
+        }
+        .chain_to_previous();
+
+        // this must return promptly; without SA_NODEFER the re-raise below stays pending behind
+        // our own trampoline and this would hang forever instead
+        scope
+            .run(|| unsafe { libc::raise(Signal::SIGCHLD as c_int) })
+            .unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn multi_signal_scope_dispatches_each_signal_and_tears_down_both_on_exit() {
+        let _guard = SIGNAL_TEST_LOCK.lock().unwrap();
+        // ignore both up front, so each signal has a known prior disposition for the scope to
+        // restore on exit
+        unsafe {
+            libc::signal(Signal::SIGUSR1 as c_int, libc::SIG_IGN);
+            libc::signal(Signal::SIGUSR2 as c_int, libc::SIG_IGN);
+        }
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let calls2 = calls.clone();
+        let scope = unsafe {
+            SignalScope::new(
+                [Signal::SIGUSR1, Signal::SIGUSR2],
+                SaFlags::empty(),
+                SigSet::empty(),
+                move |signo, _| {
+                    calls2.lock().unwrap().push(signo);
+                    true
+                },
+            )
+        };
+
+        scope
+            .run(|| {
+                unsafe { libc::raise(Signal::SIGUSR1 as c_int) };
+                unsafe { libc::raise(Signal::SIGUSR2 as c_int) };
+            })
+            .unwrap();
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![Signal::SIGUSR1 as u8, Signal::SIGUSR2 as u8]
+        );
+
+        // the scope has exited, so both signals' prior SIG_IGN dispositions must be back, not just
+        // one; raising either again should therefore be silently ignored rather than reaching our
+        // handler (or falling back to the default, which would terminate the test process)
+        unsafe { libc::raise(Signal::SIGUSR1 as c_int) };
+        unsafe { libc::raise(Signal::SIGUSR2 as c_int) };
+        assert_eq!(calls.lock().unwrap().len(), 2);
+
+        unsafe {
+            libc::signal(Signal::SIGUSR1 as c_int, libc::SIG_DFL);
+            libc::signal(Signal::SIGUSR2 as c_int, libc::SIG_DFL);
+        }
+    }
+
+    #[test]
+    fn scope_runs_handler_on_the_alternate_stack_when_requested() {
+        let _guard = SIGNAL_TEST_LOCK.lock().unwrap();
+
+        // sentinel starts as neither 0 nor SS_ONSTACK so a handler that never runs is caught too
+        let ss_flags = Arc::new(AtomicUsize::new(usize::MAX));
+        let ss_flags2 = ss_flags.clone();
+
+        let scope = unsafe {
+            SignalScope::new(
+                [Signal::SIGUSR1],
+                SaFlags::empty(),
+                SigSet::empty(),
+                move |_, _| {
+                    // query (rather than set) the current alternate stack state: passing a null
+                    // `ss` leaves the disposition untouched and only fills in `old`, whose
+                    // SS_ONSTACK bit tells us whether we're currently executing on it
+                    let mut current = mem::zeroed::<libc::stack_t>();
+                    libc::sigaltstack(std::ptr::null(), &mut current);
+                    ss_flags2.store(current.ss_flags as usize, Ordering::SeqCst);
+                    true
+                },
+            )
+        }
+        .on_alt_stack(1 << 16);
+
+        scope
+            .run(|| unsafe { libc::raise(Signal::SIGUSR1 as c_int) })
+            .unwrap();
+
+        assert_eq!(ss_flags.load(Ordering::SeqCst) as c_int, libc::SS_ONSTACK);
+    }
+}